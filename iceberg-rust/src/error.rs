@@ -0,0 +1,29 @@
+/*!
+ * Defines the [Error] type used throughout the crate.
+*/
+use thiserror::Error;
+
+/// Iceberg error type
+#[derive(Error, Debug)]
+pub enum Error {
+    /// Used when a value has an invalid format for its intended use.
+    #[error("{0} has an invalid format")]
+    InvalidFormat(String),
+    /// Failed to commit a transaction after exhausting its configured retries because a
+    /// concurrently committed snapshot kept conflicting with the transaction's operations.
+    #[error("Failed to commit transaction after {0} retries due to a conflicting concurrent snapshot")]
+    CommitConflict(u32),
+    /// Returned by a catalog's `update_table` when the metadata location it was
+    /// conditioned on is no longer the table's current one, i.e. a concurrent writer
+    /// committed first. Distinguished from other catalog failures (network, auth,
+    /// serialization) so [TableTransaction::commit](crate::table::transaction::TableTransaction::commit)
+    /// knows which errors are safe to retry and which must be propagated unchanged.
+    #[error("catalog rejected the commit: current metadata location does not match the expected previous location")]
+    CatalogCommitConflict,
+    /// Forwarded error from serde_json
+    #[error(transparent)]
+    JSONSerde(#[from] serde_json::Error),
+    /// Forwarded error from object_store
+    #[error(transparent)]
+    ObjectStore(#[from] object_store::Error),
+}
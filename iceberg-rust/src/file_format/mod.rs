@@ -6,7 +6,27 @@ use ::parquet::format::FileMetaData;
 pub mod parquet;
 
 /// Metadata for a datafile
+#[derive(Debug, Clone)]
 pub enum DatafileMetadata {
     /// Metadata for a parquet datafile
     Parquet(FileMetaData),
 }
+
+impl DatafileMetadata {
+    /// Number of rows in the datafile
+    pub fn row_count(&self) -> i64 {
+        match self {
+            DatafileMetadata::Parquet(file_metadata) => file_metadata.num_rows,
+        }
+    }
+    /// Size of the datafile in bytes, summed across its row groups
+    pub fn size_bytes(&self) -> i64 {
+        match self {
+            DatafileMetadata::Parquet(file_metadata) => file_metadata
+                .row_groups
+                .iter()
+                .map(|row_group| row_group.total_compressed_size)
+                .sum(),
+        }
+    }
+}
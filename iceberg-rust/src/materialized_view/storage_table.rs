@@ -1,4 +1,6 @@
 use std::{
+    collections::HashSet,
+    future::Future,
     iter::repeat,
     ops::{Deref, DerefMut},
 };
@@ -189,4 +191,148 @@ impl StorageTable {
         old.table.drop().await?;
         Ok(())
     }
+
+    /// Refresh the storage table, appending only the rows materialized from the
+    /// data added to its base tables since the last refresh instead of rebuilding
+    /// the entire table. Falls back to [`Self::full_refresh`] when `force_full_refresh`
+    /// is set or when any base table is `Invalid` and therefore has no prior
+    /// snapshot to diff against.
+    ///
+    /// `full_refresh_files` is called to materialize the complete view when falling
+    /// back to a full refresh. `incremental_files` is called with, for every base
+    /// table in the `Outdated` state, the data files that were added to it since the
+    /// recorded snapshot (computed by diffing the manifest entries reachable from
+    /// the old and current snapshots); it materializes just the corresponding new
+    /// view rows.
+    pub async fn incremental_refresh<FFull, FullFut, FInc, IncFut>(
+        &mut self,
+        version_id: VersionId,
+        base_tables: Vec<(Table, StorageTableState)>,
+        base_table_pointers: Vec<BaseTable>,
+        force_full_refresh: bool,
+        branch: Option<String>,
+        full_refresh_files: FFull,
+        incremental_files: FInc,
+    ) -> Result<(), Error>
+    where
+        FFull: FnOnce() -> FullFut,
+        FullFut: Future<Output = Result<Vec<DataFile>, Error>>,
+        FInc: FnOnce(Vec<(Table, Vec<DataFile>)>) -> IncFut,
+        IncFut: Future<Output = Result<Vec<DataFile>, Error>>,
+    {
+        if force_full_refresh
+            || base_tables
+                .iter()
+                .any(|(_, state)| matches!(state, StorageTableState::Invalid))
+        {
+            let files = full_refresh_files().await?;
+            return self
+                .full_refresh(files, version_id, base_table_pointers, branch)
+                .await;
+        }
+
+        let mut deltas = Vec::with_capacity(base_tables.len());
+        for (base_table, state) in base_tables {
+            if let StorageTableState::Outdated(old_snapshot_id) = state {
+                let current_snapshot_id = base_table
+                    .metadata()
+                    .current_snapshot(branch.as_deref())?
+                    .ok_or_else(|| Error::InvalidFormat("base table snapshot".to_string()))?
+                    .snapshot_id;
+                let added = added_data_files(
+                    &base_table,
+                    old_snapshot_id,
+                    current_snapshot_id,
+                    branch.as_deref(),
+                )
+                .await?;
+                deltas.push((base_table, added));
+            }
+        }
+
+        let files = incremental_files(deltas).await?;
+
+        self.table
+            .new_transaction(branch.as_deref())
+            .append(files)
+            .update_snapshot_summary(vec![
+                (VERSION_KEY.to_string(), serde_json::to_string(&version_id)?),
+                (
+                    BASE_TABLES_KEY.to_string(),
+                    serde_json::to_string(&base_table_pointers)?,
+                ),
+            ])
+            .commit()
+            .await?;
+        Ok(())
+    }
+}
+
+/// The data files present as of `new_snapshot_id` but not yet present as of
+/// `old_snapshot_id`, found by diffing the manifest entries reachable from each
+/// snapshot.
+async fn added_data_files(
+    table: &Table,
+    old_snapshot_id: i64,
+    new_snapshot_id: i64,
+    branch: Option<&str>,
+) -> Result<Vec<DataFile>, Error> {
+    let old_files = table.datafiles(Some(old_snapshot_id), branch).await?;
+    let new_files = table.datafiles(Some(new_snapshot_id), branch).await?;
+    Ok(diff_new_files(&old_files, new_files))
+}
+
+/// The files in `new_files` whose path isn't among `old_files`, i.e. the ones
+/// added between the two snapshots `new_files`/`old_files` were read from.
+fn diff_new_files(old_files: &[DataFile], new_files: Vec<DataFile>) -> Vec<DataFile> {
+    let old_paths: HashSet<&str> = old_files.iter().map(|file| file.file_path.as_str()).collect();
+    new_files
+        .into_iter()
+        .filter(|file| !old_paths.contains(file.file_path.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use iceberg_rust_spec::spec::manifest::{Content, DataFileBuilder, DataFileFormat};
+
+    use super::*;
+
+    fn data_file(path: &str) -> DataFile {
+        DataFileBuilder::default()
+            .content(Content::Data)
+            .file_path(path.to_string())
+            .file_format(DataFileFormat::Parquet)
+            .record_count(1)
+            .file_size_in_bytes(1)
+            .sort_order_id(Some(0))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn diff_new_files_keeps_only_unseen_paths() {
+        let old_files = vec![data_file("a.parquet")];
+        let new_files = vec![data_file("a.parquet"), data_file("b.parquet")];
+
+        let added = diff_new_files(&old_files, new_files);
+        assert_eq!(added.len(), 1);
+        assert_eq!(added[0].file_path, "b.parquet");
+    }
+
+    #[test]
+    fn diff_new_files_empty_old_keeps_everything() {
+        let new_files = vec![data_file("a.parquet"), data_file("b.parquet")];
+
+        let added = diff_new_files(&[], new_files);
+        assert_eq!(added.len(), 2);
+    }
+
+    #[test]
+    fn diff_new_files_no_new_files_is_empty() {
+        let old_files = vec![data_file("a.parquet")];
+
+        let added = diff_new_files(&old_files, vec![]);
+        assert!(added.is_empty());
+    }
 }
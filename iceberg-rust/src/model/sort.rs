@@ -1,10 +1,40 @@
 /**
  * Sorting
 */
+use std::sync::Arc;
+
+use arrow::{
+    array::ArrayRef,
+    compute::{lexsort_to_indices, SortColumn, SortOptions},
+};
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+
 use super::partition::Transform;
 
+/// Convert between this crate's [SortOrder], which carries the write-time
+/// [SortOrder::sort_indices] logic, and the wire-format
+/// [iceberg_rust_spec::spec::sort::SortOrder] stored on [TableMetadata](iceberg_rust_spec::spec::table_metadata::TableMetadata).
+/// The two describe the same document, so the conversion goes through JSON rather
+/// than field-by-field mapping, matching how [crate::spec::table_metadata] bridges
+/// format versions.
+impl TryFrom<&SortOrder> for iceberg_rust_spec::spec::sort::SortOrder {
+    type Error = Error;
+
+    fn try_from(order: &SortOrder) -> Result<Self, Error> {
+        Ok(serde_json::from_value(serde_json::to_value(order)?)?)
+    }
+}
+
+impl TryFrom<&iceberg_rust_spec::spec::sort::SortOrder> for SortOrder {
+    type Error = Error;
+
+    fn try_from(order: &iceberg_rust_spec::spec::sort::SortOrder) -> Result<Self, Error> {
+        Ok(serde_json::from_value(serde_json::to_value(order)?)?)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 /// Sort direction in a partition, either ascending or descending
 pub enum SortDirection {
@@ -41,7 +71,7 @@ pub struct SortField {
     pub null_order: NullOrder,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 #[serde(rename_all = "kebab-case")]
 /// A sort order is defined by a sort order id and a list of sort fields.
 /// The order of the sort fields within the list defines the order in which the sort is applied to the data.
@@ -52,6 +82,36 @@ pub struct SortOrder {
     pub fields: Vec<SortField>,
 }
 
+impl SortOrder {
+    /// Compute the row permutation that sorts `source_columns` according to this
+    /// sort order. `source_columns` must contain one array per [SortField], in the
+    /// same order as `self.fields`, holding the values of each field's `source_id`
+    /// column. Each field's [Transform] is applied to its column before comparing,
+    /// so e.g. a `bucket[n]` sort field sorts by bucket rather than by the raw value,
+    /// and its `direction`/`null_order` control whether values sort ascending or
+    /// descending and where nulls are placed. Rows that tie on a field are ordered
+    /// by the fields after it.
+    pub fn sort_indices(&self, source_columns: &[ArrayRef]) -> Result<Vec<u32>, Error> {
+        let columns = self
+            .fields
+            .iter()
+            .zip(source_columns)
+            .map(|(field, source)| {
+                Ok(SortColumn {
+                    values: field.transform.apply(source)?,
+                    options: Some(SortOptions {
+                        descending: field.direction == SortDirection::Descending,
+                        nulls_first: field.null_order == NullOrder::First,
+                    }),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let indices = lexsort_to_indices(&columns, None)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        Ok(indices.values().to_vec())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,4 +164,42 @@ mod tests {
         assert_eq!(SortDirection::Descending, order.fields[1].direction);
         assert_eq!(NullOrder::Last, order.fields[1].null_order);
     }
+
+    #[test]
+    fn sort_indices_orders_by_field_ascending() {
+        use arrow::array::Int32Array;
+
+        let order = SortOrder {
+            order_id: 1,
+            fields: vec![SortField {
+                source_id: 1,
+                transform: Transform::Identity,
+                direction: SortDirection::Ascending,
+                null_order: NullOrder::First,
+            }],
+        };
+        let column: ArrayRef = Arc::new(Int32Array::from(vec![3, 1, 2]));
+
+        let indices = order.sort_indices(&[column]).unwrap();
+        assert_eq!(indices, vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn sort_indices_orders_by_field_descending() {
+        use arrow::array::Int32Array;
+
+        let order = SortOrder {
+            order_id: 1,
+            fields: vec![SortField {
+                source_id: 1,
+                transform: Transform::Identity,
+                direction: SortDirection::Descending,
+                null_order: NullOrder::Last,
+            }],
+        };
+        let column: ArrayRef = Arc::new(Int32Array::from(vec![3, 1, 2]));
+
+        let indices = order.sort_indices(&[column]).unwrap();
+        assert_eq!(indices, vec![0, 2, 1]);
+    }
 }
@@ -0,0 +1,260 @@
+/*!
+ * Compatibility layer for reading and writing table metadata documents across
+ * Iceberg spec format versions. The in-memory [TableMetadata] model is always
+ * v2-shaped; this is the only place in the crate that needs to know what the v1
+ * wire format looked like, or that a document might carry fields from a spec
+ * version newer than either of them.
+*/
+use std::collections::{HashMap, HashSet};
+
+use iceberg_rust_spec::spec::{
+    partition::{PartitionField, PartitionSpec},
+    schema::SchemaV2,
+    snapshot::Snapshot,
+    sort::SortOrder,
+    table_metadata::{FormatVersion, TableMetadata, TableMetadataBuilder},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::error::Error;
+
+/// Top-level JSON keys present on a metadata document that this crate's model
+/// doesn't know about, e.g. because it was written by a newer, not-yet-supported
+/// spec version. Kept alongside a [TableMetadata] so that reading and rewriting a
+/// document through this crate doesn't silently drop them.
+pub type UnknownFields = Map<String, Value>;
+
+/// The Iceberg v1 table metadata document, trimmed to the fields this crate
+/// understands and can upgrade into v2. See the ["Version 1" section of the
+/// Iceberg table spec](https://iceberg.apache.org/spec/#version-1-metadata-fields).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TableMetadataV1 {
+    /// Added to the spec after the original v1 documents already in the wild, so
+    /// older ones may not carry it; synthesize one rather than fail to read them.
+    #[serde(default = "Uuid::new_v4")]
+    table_uuid: Uuid,
+    location: String,
+    last_updated_ms: i64,
+    schema: SchemaV2,
+    partition_spec: Vec<PartitionField>,
+    #[serde(default)]
+    properties: HashMap<String, String>,
+    #[serde(default)]
+    current_snapshot_id: Option<i64>,
+    #[serde(default)]
+    snapshots: Vec<Snapshot>,
+}
+
+impl TableMetadataV1 {
+    /// Carry every field v1 actually stores straight through to the v2 model
+    /// (`table_uuid`, `location`, `last_updated_ms`, `properties`,
+    /// `current_snapshot_id`, `snapshots`), and synthesize only the fields v2
+    /// requires that v1 has no concept of at all:
+    /// * a `schemas`/`current_schema_id` pair, from the single `schema` (its
+    ///   `source_id`/field ids are already assigned, since v1 schemas carry them
+    ///   too -- v1 just never had to say so explicitly via a `schemas` list);
+    /// * a `partition_specs`/`default_spec_id` pair with id `0`, from the single
+    ///   `partition_spec`;
+    /// * a `sort_orders` list containing only the unsorted order (id `0`), since
+    ///   v1 has no concept of sort orders at all;
+    /// * a `last_sequence_number` of `0`, since v1 doesn't track sequence numbers.
+    ///
+    /// The result still declares format version 1, so [to_vec] writes it back as v1.
+    fn upgrade(self) -> Result<TableMetadata, Error> {
+        let current_schema_id = self.schema.schema_id;
+        Ok(TableMetadataBuilder::default()
+            .format_version(FormatVersion::V1)
+            .table_uuid(self.table_uuid)
+            .location(self.location)
+            .last_updated_ms(self.last_updated_ms)
+            .schemas(vec![self.schema])
+            .current_schema_id(current_schema_id)
+            .partition_specs(vec![PartitionSpec {
+                spec_id: 0,
+                fields: self.partition_spec,
+            }])
+            .default_spec_id(0)
+            .sort_orders(vec![SortOrder {
+                order_id: 0,
+                fields: vec![],
+            }])
+            .default_sort_order_id(0)
+            .last_sequence_number(0)
+            .properties(self.properties)
+            .current_snapshot_id(self.current_snapshot_id)
+            .snapshots(self.snapshots)
+            .build()?)
+    }
+
+    /// The inverse of [Self::upgrade]: project a v2 model back down to what a v1
+    /// document can express, for [to_vec] to write out when the table declares
+    /// format version 1. Fields v1 has no concept of (sort orders, sequence
+    /// numbers, multiple schemas/specs) are dropped, same as they were
+    /// synthesized on the way up.
+    fn downgrade(metadata: &TableMetadata) -> Result<Self, Error> {
+        let schema = metadata
+            .schemas
+            .iter()
+            .find(|schema| schema.schema_id == metadata.current_schema_id)
+            .ok_or_else(|| Error::InvalidFormat("current schema".to_string()))?
+            .clone();
+        let partition_spec = metadata
+            .partition_specs
+            .iter()
+            .find(|spec| spec.spec_id == metadata.default_spec_id)
+            .map(|spec| spec.fields.clone())
+            .unwrap_or_default();
+        Ok(TableMetadataV1 {
+            table_uuid: metadata.table_uuid,
+            location: metadata.location.clone(),
+            last_updated_ms: metadata.last_updated_ms,
+            schema,
+            partition_spec,
+            properties: metadata.properties.clone(),
+            current_snapshot_id: metadata.current_snapshot_id,
+            snapshots: metadata.snapshots.clone(),
+        })
+    }
+}
+
+/// Deserialize a table metadata document, whichever of the supported format
+/// versions (`1` or `2`) it declares, upgrading a v1 document to the v2 in-memory
+/// model. Returns the model alongside any top-level JSON keys this crate doesn't
+/// recognize, so a later [to_vec] can write them back unchanged.
+pub fn from_slice(bytes: &[u8]) -> Result<(TableMetadata, UnknownFields), Error> {
+    let value: Value = serde_json::from_slice(bytes)?;
+    let object = value
+        .as_object()
+        .ok_or_else(|| Error::InvalidFormat("table metadata".to_string()))?;
+    let format_version = object
+        .get("format-version")
+        .and_then(Value::as_i64)
+        .ok_or_else(|| Error::InvalidFormat("table metadata format-version".to_string()))?;
+
+    match format_version {
+        1 => {
+            let v1: TableMetadataV1 = serde_json::from_value(value.clone())?;
+            let known = serde_json::to_value(&v1)?;
+            let unknown = unknown_fields(object, &known);
+            Ok((v1.upgrade()?, unknown))
+        }
+        2 => {
+            let metadata: TableMetadata = serde_json::from_value(value.clone())?;
+            let known = serde_json::to_value(&metadata)?;
+            let unknown = unknown_fields(object, &known);
+            Ok((metadata, unknown))
+        }
+        other => Err(Error::InvalidFormat(format!(
+            "table metadata format-version {other}"
+        ))),
+    }
+}
+
+/// Serialize `metadata` in whichever format version it declares, re-merging any
+/// `unknown` top-level keys captured by [from_slice] so that a read-modify-write
+/// round trip through this crate doesn't lose fields it doesn't itself understand.
+pub fn to_vec(metadata: &TableMetadata, unknown: &UnknownFields) -> Result<Vec<u8>, Error> {
+    let mut value = match metadata.format_version {
+        FormatVersion::V1 => {
+            let mut value = serde_json::to_value(TableMetadataV1::downgrade(metadata)?)?;
+            if let Some(object) = value.as_object_mut() {
+                object.insert("format-version".to_string(), Value::from(1));
+            }
+            value
+        }
+        FormatVersion::V2 => serde_json::to_value(metadata)?,
+    };
+    if let Some(object) = value.as_object_mut() {
+        for (key, field_value) in unknown {
+            object.entry(key.clone()).or_insert_with(|| field_value.clone());
+        }
+    }
+    Ok(serde_json::to_vec(&value)?)
+}
+
+/// The keys present in `document` that aren't among the keys `known` serializes
+/// to, i.e. fields this crate's model doesn't round-trip on its own.
+fn unknown_fields(document: &Map<String, Value>, known: &Value) -> UnknownFields {
+    let known_keys: HashSet<&str> = known
+        .as_object()
+        .map(|object| object.keys().map(String::as_str).collect())
+        .unwrap_or_default();
+    document
+        .iter()
+        .filter(|(key, _)| !known_keys.contains(key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1_DOCUMENT: &str = r#"
+    {
+        "format-version": 1,
+        "table-uuid": "9c12d441-03fe-4693-9a96-a0705ddf69c1",
+        "location": "s3://bucket/table",
+        "last-updated-ms": 1600000000000,
+        "schema": {
+            "type": "struct",
+            "schema-id": 0,
+            "fields": [
+                { "id": 1, "name": "id", "required": true, "type": "long" }
+            ]
+        },
+        "partition-spec": [],
+        "properties": {
+            "write.format.default": "parquet"
+        },
+        "current-snapshot-id": 1,
+        "snapshots": [
+            {
+                "snapshot-id": 1,
+                "timestamp-ms": 1600000000000,
+                "summary": { "operation": "append" },
+                "manifest-list": "s3://bucket/table/metadata/snap-1.json",
+                "schema-id": 0
+            }
+        ]
+    }
+    "#;
+
+    #[test]
+    fn upgrade_carries_properties_and_snapshots() {
+        let (metadata, _) = from_slice(V1_DOCUMENT.as_bytes()).unwrap();
+        assert_eq!(
+            metadata.properties.get("write.format.default").unwrap(),
+            "parquet"
+        );
+        assert_eq!(metadata.current_snapshot_id, Some(1));
+        assert_eq!(metadata.snapshots.len(), 1);
+        assert_eq!(metadata.snapshots[0].snapshot_id, 1);
+    }
+
+    /// A v1 document's `properties`/`snapshots` must survive a full
+    /// upgrade -> commit (`to_vec`) -> read (`from_slice`) round trip, not just
+    /// the initial upgrade -- this is what a real commit path exercises.
+    #[test]
+    fn v1_round_trip_preserves_properties_and_snapshots() {
+        let (metadata, unknown) = from_slice(V1_DOCUMENT.as_bytes()).unwrap();
+
+        let bytes = to_vec(&metadata, &unknown).unwrap();
+        let value: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(value["format-version"], Value::from(1));
+
+        let (roundtripped, _) = from_slice(&bytes).unwrap();
+        assert_eq!(
+            roundtripped.properties.get("write.format.default").unwrap(),
+            "parquet"
+        );
+        assert_eq!(roundtripped.current_snapshot_id, Some(1));
+        assert_eq!(roundtripped.snapshots.len(), 1);
+        assert_eq!(roundtripped.snapshots[0].snapshot_id, 1);
+        assert_eq!(roundtripped.table_uuid, metadata.table_uuid);
+        assert_eq!(roundtripped.last_updated_ms, metadata.last_updated_ms);
+    }
+}
@@ -0,0 +1,530 @@
+/*!
+ * Defines [Table], the in-memory handle to an Iceberg table that
+ * [TableTransaction](transaction::TableTransaction)s and
+ * [StorageTable](crate::materialized_view::storage_table::StorageTable) operate on.
+*/
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use arrow::{
+    array::{ArrayRef, BooleanArray, Int64Array, StringArray},
+    datatypes::{DataType, Field, Schema as ArrowSchema},
+    record_batch::RecordBatch,
+};
+use datafusion_common::DFSchema;
+use datafusion_expr::{execution_props::ExecutionProps, Expr};
+use datafusion_physical_expr::create_physical_expr;
+use futures::StreamExt;
+use iceberg_rust_spec::spec::{
+    manifest::{Content, DataFile, DataFileBuilder, DataFileFormat},
+    snapshot::{Operation as SnapshotOperation, Snapshot, Summary},
+    table_metadata::TableMetadata,
+};
+use object_store::ObjectStore;
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    catalog::{identifier::Identifier, Catalog},
+    error::Error,
+    file_format::DatafileMetadata,
+    model::sort::SortOrder,
+    spec::table_metadata::UnknownFields,
+    table::transaction::{operation::RemovedFileStats, TableTransaction},
+    util::strip_prefix,
+};
+
+pub mod transaction;
+
+/// The set of data files live as of a snapshot. Tracked as a JSON sidecar next to
+/// each snapshot rather than Iceberg's avro manifest/manifest-list format, since this
+/// crate has no avro dependency; it plays the same role of enumerating which files
+/// (and, for merge-on-read deletes, which delete files) a snapshot sees.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ManifestList {
+    /// Data files live in this snapshot
+    pub data_files: Vec<DataFile>,
+    /// Delete files masking rows within the data files above, for partially-deleted
+    /// files under the v2 merge-on-read model
+    pub delete_files: Vec<DeleteFile>,
+}
+
+/// A positional delete file: the row positions it masks within a single data file.
+/// Unlike Iceberg's avro-encoded delete manifests, this crate has no avro dependency,
+/// so the file itself is still a real Parquet file with the spec's `file_path`/`pos`
+/// columns, but it's tracked here by reference rather than through a manifest entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct DeleteFile {
+    /// Path of the delete file
+    pub file_path: String,
+    /// Path of the data file this delete file masks rows in
+    pub referenced_data_file: String,
+    /// Number of rows masked
+    pub record_count: i64,
+}
+
+/// A handle to an Iceberg table: its current metadata, where that metadata document
+/// lives, and the catalog and object store it was loaded from.
+pub struct Table {
+    /// Identifier this table is registered under in its catalog
+    pub identifier: Identifier,
+    metadata: TableMetadata,
+    metadata_location: String,
+    unknown_fields: UnknownFields,
+    object_store: Arc<dyn ObjectStore>,
+    catalog: Arc<dyn Catalog>,
+}
+
+impl Table {
+    /// Create a table handle for an already-loaded metadata document.
+    pub fn new(
+        identifier: Identifier,
+        metadata: TableMetadata,
+        metadata_location: String,
+        unknown_fields: UnknownFields,
+        object_store: Arc<dyn ObjectStore>,
+        catalog: Arc<dyn Catalog>,
+    ) -> Self {
+        Table {
+            identifier,
+            metadata,
+            metadata_location,
+            unknown_fields,
+            object_store,
+            catalog,
+        }
+    }
+
+    /// The table's identifier in its catalog
+    pub fn identifier(&self) -> &Identifier {
+        &self.identifier
+    }
+    /// The table's current in-memory metadata
+    pub fn metadata(&self) -> &TableMetadata {
+        &self.metadata
+    }
+    /// Mutable access to the table's in-memory metadata
+    pub fn metadata_mut(&mut self) -> &mut TableMetadata {
+        &mut self.metadata
+    }
+    /// Location of the metadata document this table was loaded from
+    pub fn metadata_location(&self) -> &str {
+        &self.metadata_location
+    }
+    /// Top-level metadata document keys this crate doesn't itself round-trip, kept
+    /// so a commit can write them back via [crate::spec::table_metadata::to_vec]
+    pub fn metadata_unknown_fields(&self) -> &UnknownFields {
+        &self.unknown_fields
+    }
+    /// The object store backing this table's files
+    pub fn object_store(&self) -> Arc<dyn ObjectStore> {
+        self.object_store.clone()
+    }
+    /// The catalog this table is registered in
+    pub fn catalog(&self) -> Arc<dyn Catalog> {
+        self.catalog.clone()
+    }
+    /// Start a transaction against this table. `branch` is reserved for per-branch
+    /// snapshot references; only the main branch is currently supported.
+    pub fn new_transaction(&mut self, _branch: Option<&str>) -> TableTransaction<'_> {
+        TableTransaction::new(self)
+    }
+    /// Increment the table's last sequence number, as happens once per commit.
+    pub fn increment_sequence_number(&mut self) {
+        self.metadata.last_sequence_number += 1;
+    }
+
+    /// Start a new snapshot on top of the current one (if any), carrying its live
+    /// data files forward. [Operation::execute](transaction::operation::Operation::execute)
+    /// then adds or removes files from it before it's attached to the metadata.
+    pub async fn new_snapshot(&mut self) -> Result<(), Error> {
+        let snapshot_id = (Uuid::new_v4().as_u128() & (i64::MAX as u128)) as i64;
+        let parent = self.metadata.current_snapshot(None)?.cloned();
+        let manifest_list = match &parent {
+            Some(snapshot) => self.read_manifest_list(&snapshot.manifest_list).await?,
+            None => ManifestList::default(),
+        };
+        let manifest_list_location = self.manifest_list_location(snapshot_id);
+        self.write_manifest_list(&manifest_list_location, &manifest_list)
+            .await?;
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?
+            .as_millis() as i64;
+
+        let snapshot = Snapshot {
+            snapshot_id,
+            parent_snapshot_id: parent.as_ref().map(|snapshot| snapshot.snapshot_id),
+            sequence_number: self.metadata.last_sequence_number,
+            timestamp_ms,
+            manifest_list: manifest_list_location,
+            schema_id: Some(self.metadata.current_schema_id),
+            summary: Summary {
+                operation: SnapshotOperation::Append,
+                other: Default::default(),
+            },
+        };
+        self.metadata.snapshots.push(snapshot);
+        self.metadata.current_snapshot_id = Some(snapshot_id);
+        Ok(())
+    }
+
+    /// The data files live as of `snapshot_id` (or the current snapshot, if `None`).
+    pub async fn datafiles(
+        &self,
+        snapshot_id: Option<i64>,
+        branch: Option<&str>,
+    ) -> Result<Vec<DataFile>, Error> {
+        let snapshot = match snapshot_id {
+            Some(id) => self
+                .metadata
+                .snapshots
+                .iter()
+                .find(|snapshot| snapshot.snapshot_id == id)
+                .cloned()
+                .ok_or_else(|| Error::InvalidFormat(format!("snapshot {id}")))?,
+            None => self
+                .metadata
+                .current_snapshot(branch)?
+                .cloned()
+                .ok_or_else(|| Error::InvalidFormat("table snapshot".to_string()))?,
+        };
+        Ok(self
+            .read_manifest_list(&snapshot.manifest_list)
+            .await?
+            .data_files)
+    }
+
+    /// Append `paths` as new data files of the current snapshot. If `sort_order` is
+    /// given, each file's rows are sorted according to it before being registered, so
+    /// the recorded sort-order-id is accurate.
+    pub async fn append_data_files(
+        &mut self,
+        paths: Vec<(String, DatafileMetadata)>,
+        sort_order: Option<&SortOrder>,
+    ) -> Result<(), Error> {
+        let mut added = Vec::with_capacity(paths.len());
+        for (path, metadata) in paths {
+            if let Some(order) = sort_order {
+                self.sort_data_file(&path, order).await?;
+            }
+            added.push(self.data_file_for(path, &metadata)?);
+        }
+
+        let snapshot = self
+            .metadata
+            .current_snapshot(None)?
+            .cloned()
+            .ok_or_else(|| Error::InvalidFormat("table snapshot".to_string()))?;
+        let mut manifest_list = self.read_manifest_list(&snapshot.manifest_list).await?;
+        manifest_list.data_files.extend(added);
+        self.write_manifest_list(&snapshot.manifest_list, &manifest_list)
+            .await?;
+        Ok(())
+    }
+
+    fn data_file_for(&self, path: String, metadata: &DatafileMetadata) -> Result<DataFile, Error> {
+        DataFileBuilder::default()
+            .content(Content::Data)
+            .file_path(path)
+            .file_format(DataFileFormat::Parquet)
+            .record_count(metadata.row_count())
+            .file_size_in_bytes(metadata.size_bytes())
+            .sort_order_id(Some(self.metadata.default_sort_order_id))
+            .build()
+            .map_err(|err| Error::InvalidFormat(err.to_string()))
+    }
+
+    /// Delete every row matching `predicate`, per the Iceberg v2 merge-on-read model:
+    /// a data file fully covered by the predicate is dropped from the manifest list
+    /// outright; a file only partially covered is kept, with a positional delete file
+    /// written alongside it and registered against it to mask the matching rows.
+    pub async fn delete_data_files(&mut self, predicate: Expr) -> Result<RemovedFileStats, Error> {
+        let snapshot = self
+            .metadata
+            .current_snapshot(None)?
+            .cloned()
+            .ok_or_else(|| Error::InvalidFormat("table snapshot".to_string()))?;
+        let mut manifest_list = self.read_manifest_list(&snapshot.manifest_list).await?;
+
+        let mut removed = RemovedFileStats::default();
+        let mut kept_data_files = Vec::with_capacity(manifest_list.data_files.len());
+        let mut kept_delete_files = manifest_list.delete_files;
+
+        for file in manifest_list.data_files {
+            let batch = self.read_parquet(&file.file_path).await?;
+            let mask = evaluate_predicate(&batch, &predicate)?;
+            let matched = mask.iter().filter(|matched| matched.unwrap_or(false)).count();
+
+            if matched == 0 {
+                kept_data_files.push(file);
+                continue;
+            }
+
+            removed.records += matched as i64;
+            kept_delete_files.retain(|delete| delete.referenced_data_file != file.file_path);
+
+            if matched == batch.num_rows() {
+                removed.data_files += 1;
+                removed.files_size += file.file_size_in_bytes;
+                continue;
+            }
+
+            let delete_file_path = positional_delete_path(&file.file_path);
+            self.write_positional_deletes(&delete_file_path, &file.file_path, &mask)
+                .await?;
+            kept_delete_files.push(DeleteFile {
+                file_path: delete_file_path,
+                referenced_data_file: file.file_path.clone(),
+                record_count: matched as i64,
+            });
+            kept_data_files.push(file);
+        }
+
+        manifest_list.data_files = kept_data_files;
+        manifest_list.delete_files = kept_delete_files;
+        self.write_manifest_list(&snapshot.manifest_list, &manifest_list)
+            .await?;
+        Ok(removed)
+    }
+
+    /// Atomically remove the data files at `deleted` and add `added` to the current
+    /// snapshot's manifest list. Any delete file referencing a removed data file is
+    /// dropped along with it.
+    pub async fn overwrite_data_files(
+        &mut self,
+        deleted: Vec<String>,
+        added: Vec<(String, DatafileMetadata)>,
+    ) -> Result<RemovedFileStats, Error> {
+        let snapshot = self
+            .metadata
+            .current_snapshot(None)?
+            .cloned()
+            .ok_or_else(|| Error::InvalidFormat("table snapshot".to_string()))?;
+        let mut manifest_list = self.read_manifest_list(&snapshot.manifest_list).await?;
+
+        let deleted_set: HashSet<&str> = deleted.iter().map(String::as_str).collect();
+        let mut removed = RemovedFileStats::default();
+        manifest_list.data_files.retain(|file| {
+            if deleted_set.contains(file.file_path.as_str()) {
+                removed.data_files += 1;
+                removed.records += file.record_count;
+                removed.files_size += file.file_size_in_bytes;
+                false
+            } else {
+                true
+            }
+        });
+        manifest_list
+            .delete_files
+            .retain(|delete| !deleted_set.contains(delete.referenced_data_file.as_str()));
+
+        for (path, metadata) in &added {
+            manifest_list
+                .data_files
+                .push(self.data_file_for(path.clone(), metadata)?);
+        }
+
+        self.write_manifest_list(&snapshot.manifest_list, &manifest_list)
+            .await?;
+        Ok(removed)
+    }
+
+    /// Write a Parquet positional delete file with the spec's `file_path`/`pos`
+    /// columns, masking every row `mask` matched in `data_file_path`.
+    async fn write_positional_deletes(
+        &self,
+        path: &str,
+        data_file_path: &str,
+        mask: &BooleanArray,
+    ) -> Result<(), Error> {
+        let positions: Vec<i64> = mask
+            .iter()
+            .enumerate()
+            .filter_map(|(position, matched)| matched.unwrap_or(false).then_some(position as i64))
+            .collect();
+        let schema = Arc::new(ArrowSchema::new(vec![
+            Field::new("file_path", DataType::Utf8, false),
+            Field::new("pos", DataType::Int64, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![
+                    data_file_path.to_string();
+                    positions.len()
+                ])) as ArrayRef,
+                Arc::new(Int64Array::from(positions)) as ArrayRef,
+            ],
+        )
+        .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+
+        let mut buffer = Vec::new();
+        {
+            let mut writer = ArrowWriter::try_new(&mut buffer, schema, None)
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+            writer
+                .write(&batch)
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+            writer
+                .close()
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        }
+        self.object_store
+            .put(&strip_prefix(path).into(), buffer.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Read `path` as Parquet into a single, concatenated batch.
+    async fn read_parquet(&self, path: &str) -> Result<RecordBatch, Error> {
+        let location = strip_prefix(path);
+        let bytes = self
+            .object_store
+            .get(&location.into())
+            .await?
+            .bytes()
+            .await?;
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(bytes)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?
+            .build()
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        let schema = reader.schema().clone();
+        let batches = reader
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        arrow::compute::concat_batches(&schema, &batches)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))
+    }
+
+    /// Read `path` as Parquet, reorder its rows according to `order`, and write it
+    /// back in place, so the file lands pre-sorted before being registered as a data
+    /// file.
+    async fn sort_data_file(&self, path: &str, order: &SortOrder) -> Result<(), Error> {
+        let batch = self.read_parquet(path).await?;
+        let schema = batch.schema();
+
+        let source_columns = order
+            .fields
+            .iter()
+            .map(|field| self.source_column(&batch, field.source_id))
+            .collect::<Result<Vec<ArrayRef>, Error>>()?;
+        let indices = order.sort_indices(&source_columns)?;
+        let take_indices = arrow::array::UInt32Array::from(indices);
+        let sorted_columns = batch
+            .columns()
+            .iter()
+            .map(|column| arrow::compute::take(column, &take_indices, None))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        let sorted_batch = RecordBatch::try_new(schema.clone(), sorted_columns)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+
+        let mut buffer = Vec::new();
+        {
+            let mut writer = ArrowWriter::try_new(&mut buffer, schema, None)
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+            writer
+                .write(&sorted_batch)
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+            writer
+                .close()
+                .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        }
+        self.object_store
+            .put(&strip_prefix(path).into(), buffer.into())
+            .await?;
+        Ok(())
+    }
+
+    /// The column in `batch` holding the values of schema field `source_id`.
+    fn source_column(&self, batch: &RecordBatch, source_id: i32) -> Result<ArrayRef, Error> {
+        let schema = self
+            .metadata
+            .schemas
+            .iter()
+            .find(|schema| schema.schema_id == self.metadata.current_schema_id)
+            .ok_or_else(|| Error::InvalidFormat("current schema".to_string()))?;
+        let field = schema
+            .fields
+            .iter()
+            .find(|field| field.id == source_id)
+            .ok_or_else(|| Error::InvalidFormat(format!("schema field {source_id}")))?;
+        let index = batch
+            .schema()
+            .index_of(&field.name)
+            .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+        Ok(batch.column(index).clone())
+    }
+
+    pub(crate) fn manifest_list_location(&self, snapshot_id: i64) -> String {
+        format!(
+            "{}/metadata/snap-{snapshot_id}-manifest-list.json",
+            self.metadata.location
+        )
+    }
+
+    pub(crate) async fn read_manifest_list(&self, location: &str) -> Result<ManifestList, Error> {
+        let bytes = self
+            .object_store
+            .get(&strip_prefix(location).into())
+            .await?
+            .bytes()
+            .await?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    pub(crate) async fn write_manifest_list(
+        &self,
+        location: &str,
+        manifest_list: &ManifestList,
+    ) -> Result<(), Error> {
+        let bytes = serde_json::to_vec(manifest_list)?;
+        self.object_store
+            .put(&strip_prefix(location).into(), bytes.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Delete every object under this table's location.
+    pub async fn drop(self) -> Result<(), Error> {
+        let prefix = strip_prefix(&self.metadata.location);
+        let mut listing = self.object_store.list(Some(&prefix.into()));
+        while let Some(object) = listing.next().await {
+            let object = object?;
+            self.object_store.delete(&object.location).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Evaluate `predicate` against every row of `batch`, returning the per-row match
+/// mask used to pick out the rows a delete should remove.
+fn evaluate_predicate(batch: &RecordBatch, predicate: &Expr) -> Result<BooleanArray, Error> {
+    let df_schema = DFSchema::try_from(batch.schema().as_ref().clone())
+        .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+    let physical_expr = create_physical_expr(predicate, &df_schema, &ExecutionProps::new())
+        .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+    let result = physical_expr
+        .evaluate(batch)
+        .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+    let array = result
+        .into_array(batch.num_rows())
+        .map_err(|err| Error::InvalidFormat(err.to_string()))?;
+    array
+        .as_any()
+        .downcast_ref::<BooleanArray>()
+        .cloned()
+        .ok_or_else(|| Error::InvalidFormat("delete predicate must evaluate to a boolean".to_string()))
+}
+
+/// Path of the positional delete file masking rows in `data_file_path`.
+fn positional_delete_path(data_file_path: &str) -> String {
+    format!("{data_file_path}.deletes.parquet")
+}
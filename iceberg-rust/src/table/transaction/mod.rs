@@ -2,18 +2,60 @@
  * Defines the [Transaction] type that performs multiple [Operation]s with ACID properties.
 */
 
-use futures::StreamExt;
+use std::{collections::HashMap, time::Duration};
+
+use datafusion_expr::Expr;
+use iceberg_rust_spec::spec::snapshot::Summary;
 use uuid::Uuid;
 
 use crate::{
-    catalog::relation::Relation, file_format::DatafileMetadata, spec::schema::SchemaV2,
-    table::Table, util::strip_prefix,
+    catalog::relation::Relation, error::Error, file_format::DatafileMetadata,
+    model::sort::SortOrder, spec::schema::SchemaV2, spec::table_metadata, table::Table,
+    util::strip_prefix,
 };
-use anyhow::{anyhow, Result};
 
 use self::operation::Operation;
 
-mod operation;
+pub(crate) mod operation;
+
+/// Table property controlling how many times a commit is retried after losing a
+/// concurrent-write race, before giving up with [Error::CommitConflict].
+const NUM_RETRIES_PROPERTY: &str = "commit.retry.num-retries";
+/// Table property controlling the initial backoff, in milliseconds, between commit
+/// retries. The wait doubles after every failed attempt.
+const MIN_WAIT_MS_PROPERTY: &str = "commit.retry.min-wait-ms";
+
+const DEFAULT_NUM_RETRIES: u32 = 4;
+const DEFAULT_MIN_WAIT_MS: u64 = 100;
+
+const ADDED_DATA_FILES: &str = "added-data-files";
+const ADDED_RECORDS: &str = "added-records";
+const ADDED_FILES_SIZE: &str = "added-files-size";
+const DELETED_DATA_FILES: &str = "deleted-data-files";
+const DELETED_RECORDS: &str = "deleted-records";
+const REMOVED_FILES_SIZE: &str = "removed-files-size";
+const TOTAL_DATA_FILES: &str = "total-data-files";
+const TOTAL_RECORDS: &str = "total-records";
+const TOTAL_FILES_SIZE: &str = "total-files-size";
+
+fn parse_metric(other: &HashMap<String, String>, key: &str) -> i64 {
+    other.get(key).and_then(|value| value.parse().ok()).unwrap_or(0)
+}
+
+/// How long to wait before retry number `attempt` (1-indexed) of a commit that lost
+/// a concurrent-write race: doubles every attempt, starting from `min_wait_ms`. The
+/// shift is capped so it can never overflow `1u64 << shift`.
+fn backoff_wait_ms(min_wait_ms: u64, attempt: u32) -> u64 {
+    let shift = (attempt - 1).min(63);
+    min_wait_ms.saturating_mul(1u64 << shift)
+}
+
+/// A prior snapshot's summary may be missing its `total-*` keys (`parse_metric`
+/// then defaults them to 0), in which case subtracting this operation's deletions
+/// could otherwise go negative. Clamp at 0.
+fn clamp_total(previous: i64, added: i64, deleted: i64) -> i64 {
+    (previous + added - deleted).max(0)
+}
 
 /// Transactions let you perform a sequence of [Operation]s that can be committed to be performed with ACID guarantees.
 pub struct TableTransaction<'table> {
@@ -49,66 +91,266 @@ impl<'table> TableTransaction<'table> {
         self.operations.push(Operation::UpdateProperties(entries));
         self
     }
+    /// Add custom entries to the new snapshot's summary
+    pub fn update_snapshot_summary(mut self, entries: Vec<(String, String)>) -> Self {
+        self.operations
+            .push(Operation::UpdateSnapshotSummary(entries));
+        self
+    }
+    /// Register a new sort order and make it the table's default. Files appended
+    /// after this is committed are written pre-sorted according to it.
+    pub fn replace_sort_order(mut self, sort_order: SortOrder) -> Self {
+        self.operations.push(Operation::ReplaceSortOrder(sort_order));
+        self
+    }
+    /// Delete all rows matching `predicate`. Data files fully covered by the
+    /// predicate are dropped; partially matching files are kept and masked with a
+    /// delete file, per the Iceberg v2 merge-on-read model.
+    pub fn delete(mut self, predicate: Expr) -> Self {
+        self.operations.push(Operation::NewDelete { predicate });
+        self
+    }
+    /// Atomically remove the data files at `deleted` and add `added`, as a single
+    /// snapshot.
+    pub fn overwrite(
+        mut self,
+        deleted: Vec<String>,
+        added: Vec<(String, DatafileMetadata)>,
+    ) -> Self {
+        self.operations
+            .push(Operation::NewOverwrite { deleted, added });
+        self
+    }
     /// Commit the transaction to perform the [Operation]s with ACID guarantees.
-    pub async fn commit(self) -> Result<()> {
+    ///
+    /// Uses optimistic concurrency control: the table's metadata as seen when the
+    /// transaction was created is the "base" the operations were planned against. If
+    /// another writer commits a new snapshot first, the base has moved out from under
+    /// us, so we reload the table's current metadata from the catalog, check that our
+    /// operations still apply cleanly to it (see [Operation::validate_rebase]), and
+    /// retry by rebuilding and recommitting the new metadata. Retries are bounded and
+    /// backed off exponentially, controlled by the `commit.retry.num-retries` and
+    /// `commit.retry.min-wait-ms` table properties. Once the retries are exhausted,
+    /// [Error::CommitConflict] is returned.
+    ///
+    /// The new metadata is written out via [table_metadata::to_vec], in whichever
+    /// format version the table declares, so that fields belonging to a spec
+    /// version newer than the ones this crate understands survive the round trip
+    /// instead of being silently dropped.
+    pub async fn commit(self) -> Result<(), Error> {
         let object_store = self.table.object_store();
         let catalog = self.table.catalog();
         let identifier = self.table.identifier.clone();
+        let operations = self.operations;
+        let table = self.table;
 
-        // Before executing the transactions operations, update the metadata for a new snapshot
-        self.table.increment_sequence_number();
-        if self.operations.iter().any(|op| match op {
-            Operation::NewAppend { paths: _ } => true,
-            _ => false,
-        }) {
-            self.table.new_snapshot().await?;
-        }
-        // Execute the table operations
-        let table = futures::stream::iter(self.operations)
-            .fold(
-                Ok::<&mut Table, anyhow::Error>(self.table),
-                |table, op| async move {
-                    let table = table?;
-                    op.execute(table).await?;
-                    Ok(table)
-                },
-            )
-            .await?;
-        // Write the new state to the object store
-
-        let transaction_uuid = Uuid::new_v4();
-        let version = &&table.metadata().last_sequence_number;
-        let metadata_json =
-            serde_json::to_string(&table.metadata()).map_err(|err| anyhow!(err.to_string()))?;
-        let metadata_file_location = table.metadata().location.to_string()
-            + "/metadata/"
-            + &version.to_string()
-            + "-"
-            + &transaction_uuid.to_string()
-            + ".metadata.json";
-        object_store
-            .put(
-                &strip_prefix(&metadata_file_location).into(),
-                metadata_json.into(),
-            )
-            .await
-            .map_err(|err| anyhow!(err.to_string()))?;
-        let previous_metadata_file_location = table.metadata_location();
-        if let Relation::Table(new_table) = catalog
-            .clone()
-            .update_table(
-                identifier,
-                metadata_file_location.as_ref(),
-                previous_metadata_file_location,
-            )
-            .await?
-        {
-            *table = new_table;
-            Ok(())
-        } else {
-            Err(anyhow!(
-                "Updating the table for the transaction didn't return a table."
-            ))
+        let properties = &table.metadata().properties;
+        let num_retries = properties
+            .get(NUM_RETRIES_PROPERTY)
+            .and_then(|x| x.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_NUM_RETRIES);
+        let min_wait_ms = properties
+            .get(MIN_WAIT_MS_PROPERTY)
+            .and_then(|x| x.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MIN_WAIT_MS);
+
+        let mut attempt = 0;
+        loop {
+            if attempt > 0 {
+                // Someone else committed ahead of us. Reload the table's current
+                // metadata and make sure our operations still make sense against it
+                // before we rebuild and retry.
+                match catalog.clone().load_table(&identifier).await? {
+                    Relation::Table(fresh) => *table = fresh,
+                    _ => {
+                        return Err(Error::InvalidFormat(
+                            "Reloading the table for the transaction didn't return a table."
+                                .to_string(),
+                        ))
+                    }
+                }
+                for op in &operations {
+                    op.validate_rebase(table).await?;
+                }
+            }
+
+            let previous_metadata_file_location = table.metadata_location().to_string();
+            let previous_totals = table.metadata().current_snapshot(None)?.map(|snapshot| {
+                (
+                    parse_metric(&snapshot.summary.other, TOTAL_DATA_FILES),
+                    parse_metric(&snapshot.summary.other, TOTAL_RECORDS),
+                    parse_metric(&snapshot.summary.other, TOTAL_FILES_SIZE),
+                )
+            });
+
+            // Before executing the transactions operations, update the metadata for a new snapshot
+            table.increment_sequence_number();
+            let creates_snapshot = operations.iter().any(|op| match op {
+                Operation::NewAppend { paths: _ }
+                | Operation::NewDelete { predicate: _ }
+                | Operation::NewOverwrite {
+                    deleted: _,
+                    added: _,
+                } => true,
+                _ => false,
+            });
+            if creates_snapshot {
+                table.new_snapshot().await?;
+            }
+            // Execute the table operations, accumulating each operation's contribution
+            // to the new snapshot's summary as it runs.
+            let mut added_data_files = 0i64;
+            let mut added_records = 0i64;
+            let mut added_files_size = 0i64;
+            let mut deleted_data_files = 0i64;
+            let mut deleted_records = 0i64;
+            let mut deleted_files_size = 0i64;
+            let mut snapshot_operation = None;
+            for op in &operations {
+                let metrics = op.execute(table).await?;
+                added_data_files += metrics.added_data_files;
+                added_records += metrics.added_records;
+                added_files_size += metrics.added_files_size;
+                deleted_data_files += metrics.deleted_data_files;
+                deleted_records += metrics.deleted_records;
+                deleted_files_size += metrics.deleted_files_size;
+                snapshot_operation = snapshot_operation.or_else(|| op.snapshot_operation());
+            }
+            if creates_snapshot {
+                let (total_data_files, total_records, total_files_size) =
+                    previous_totals.unwrap_or((0, 0, 0));
+                let mut other = HashMap::new();
+                other.insert(ADDED_DATA_FILES.to_string(), added_data_files.to_string());
+                other.insert(ADDED_RECORDS.to_string(), added_records.to_string());
+                other.insert(ADDED_FILES_SIZE.to_string(), added_files_size.to_string());
+                other.insert(
+                    DELETED_DATA_FILES.to_string(),
+                    deleted_data_files.to_string(),
+                );
+                other.insert(DELETED_RECORDS.to_string(), deleted_records.to_string());
+                other.insert(
+                    REMOVED_FILES_SIZE.to_string(),
+                    deleted_files_size.to_string(),
+                );
+                other.insert(
+                    TOTAL_DATA_FILES.to_string(),
+                    clamp_total(total_data_files, added_data_files, deleted_data_files)
+                        .to_string(),
+                );
+                other.insert(
+                    TOTAL_RECORDS.to_string(),
+                    clamp_total(total_records, added_records, deleted_records).to_string(),
+                );
+                other.insert(
+                    TOTAL_FILES_SIZE.to_string(),
+                    clamp_total(total_files_size, added_files_size, deleted_files_size)
+                        .to_string(),
+                );
+                for op in &operations {
+                    other.extend(op.summary_entries());
+                }
+                if let Some(snapshot) = table.metadata_mut().current_snapshot_mut(None)? {
+                    snapshot.summary = Summary {
+                        operation: snapshot_operation.unwrap_or(
+                            iceberg_rust_spec::spec::snapshot::Operation::Append,
+                        ),
+                        other,
+                    };
+                }
+            }
+            // Write the new state to the object store
+
+            let transaction_uuid = Uuid::new_v4();
+            let version = &&table.metadata().last_sequence_number;
+            // Write back whichever format version the table declares, preserving any
+            // fields from a newer spec version that this crate doesn't itself
+            // understand rather than dropping them on every commit.
+            let metadata_bytes =
+                table_metadata::to_vec(table.metadata(), table.metadata_unknown_fields())?;
+            let metadata_file_location = table.metadata().location.to_string()
+                + "/metadata/"
+                + &version.to_string()
+                + "-"
+                + &transaction_uuid.to_string()
+                + ".metadata.json";
+            object_store
+                .put(
+                    &strip_prefix(&metadata_file_location).into(),
+                    metadata_bytes.into(),
+                )
+                .await?;
+
+            match catalog
+                .clone()
+                .update_table(
+                    identifier.clone(),
+                    metadata_file_location.as_ref(),
+                    &previous_metadata_file_location,
+                )
+                .await
+            {
+                Ok(Relation::Table(new_table)) => {
+                    *table = new_table;
+                    return Ok(());
+                }
+                Ok(_) => {
+                    return Err(Error::InvalidFormat(
+                        "Updating the table for the transaction didn't return a table."
+                            .to_string(),
+                    ))
+                }
+                // Only a detected conflict is retried. Any other failure (network,
+                // auth, serialization, ...) is propagated unchanged rather than being
+                // mislabeled as a conflict and swallowed.
+                Err(Error::CatalogCommitConflict) if attempt < num_retries => {
+                    attempt += 1;
+                    let wait_ms = backoff_wait_ms(min_wait_ms, attempt);
+                    tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+                }
+                Err(Error::CatalogCommitConflict) => return Err(Error::CommitConflict(num_retries)),
+                Err(err) => return Err(err),
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_each_attempt() {
+        assert_eq!(backoff_wait_ms(100, 1), 100);
+        assert_eq!(backoff_wait_ms(100, 2), 200);
+        assert_eq!(backoff_wait_ms(100, 3), 400);
+    }
+
+    #[test]
+    fn backoff_saturates_instead_of_overflowing() {
+        assert_eq!(backoff_wait_ms(u64::MAX, 2), u64::MAX);
+        assert_eq!(backoff_wait_ms(100, 100), u64::MAX);
+    }
+
+    #[test]
+    fn parse_metric_defaults_missing_key_to_zero() {
+        let other = HashMap::new();
+        assert_eq!(parse_metric(&other, TOTAL_DATA_FILES), 0);
+    }
+
+    #[test]
+    fn parse_metric_reads_present_key() {
+        let mut other = HashMap::new();
+        other.insert(TOTAL_RECORDS.to_string(), "42".to_string());
+        assert_eq!(parse_metric(&other, TOTAL_RECORDS), 42);
+    }
+
+    #[test]
+    fn clamp_total_adds_and_subtracts() {
+        assert_eq!(clamp_total(10, 5, 3), 12);
+    }
+
+    #[test]
+    fn clamp_total_floors_at_zero() {
+        assert_eq!(clamp_total(0, 0, 5), 0);
+    }
+}
@@ -0,0 +1,301 @@
+/*!
+ * Defines the [Operation] enum that represents the individual actions that can be
+ * performed as part of a [TableTransaction](crate::table::transaction::TableTransaction).
+*/
+use datafusion_expr::Expr;
+use iceberg_rust_spec::spec::snapshot::Operation as SnapshotOperation;
+
+use crate::{
+    error::Error, file_format::DatafileMetadata, model::sort::SortOrder, spec::schema::SchemaV2,
+    table::Table,
+};
+
+/// A single action that can be performed as part of a table transaction.
+#[derive(Debug, Clone)]
+pub enum Operation {
+    /// Update the schema of the table
+    UpdateSchema(SchemaV2),
+    /// Update the default partition spec of the table
+    UpdateSpec(i32),
+    /// Append new data files to the table
+    NewAppend {
+        /// Paths and metadata of the new datafiles
+        paths: Vec<(String, DatafileMetadata)>,
+    },
+    /// Update the properties of the table
+    UpdateProperties(Vec<(String, String)>),
+    /// Add custom entries to the new snapshot's summary, alongside the standard
+    /// operation type and write statistics.
+    UpdateSnapshotSummary(Vec<(String, String)>),
+    /// Register a new sort order and make it the table's default
+    ReplaceSortOrder(SortOrder),
+    /// Delete rows matching a predicate. Data files that are fully covered by the
+    /// predicate are dropped outright; data files that are only partially covered
+    /// are kept, with a positional delete file written to mask out the matching
+    /// rows on read (the Iceberg v2 merge-on-read model). Equality delete files are
+    /// not written.
+    NewDelete {
+        /// Predicate identifying the rows to delete
+        predicate: Expr,
+    },
+    /// Atomically remove a set of data files and add another set, as a single
+    /// snapshot. Used for compaction and for rewrites that can't be expressed as a
+    /// predicate (e.g. a full row-level merge/update already materialized by the
+    /// caller).
+    NewOverwrite {
+        /// Paths of the data files being removed
+        deleted: Vec<String>,
+        /// Paths and metadata of the data files being added
+        added: Vec<(String, DatafileMetadata)>,
+    },
+}
+
+/// An operation's contribution to the new snapshot's summary write statistics.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WriteMetrics {
+    /// Number of data files added
+    pub added_data_files: i64,
+    /// Number of records added
+    pub added_records: i64,
+    /// Size in bytes of the data files added
+    pub added_files_size: i64,
+    /// Number of data files removed
+    pub deleted_data_files: i64,
+    /// Number of records removed
+    pub deleted_records: i64,
+    /// Size in bytes of the data files removed
+    pub deleted_files_size: i64,
+}
+
+/// The records and bytes removed by a delete or overwrite operation, as observed by
+/// evaluating it against the manifest entries it actually touched. Only known once
+/// the operation has executed, since it depends on which existing data files a
+/// predicate matched.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RemovedFileStats {
+    /// Number of data files removed
+    pub data_files: i64,
+    /// Number of records removed
+    pub records: i64,
+    /// Size in bytes of the data files removed
+    pub files_size: i64,
+}
+
+/// Sum up the file count, record count and total size contributed by a set of new
+/// data files, as used by both [Operation::NewAppend] and the added side of
+/// [Operation::NewOverwrite].
+fn added_file_stats(paths: &[(String, DatafileMetadata)]) -> (i64, i64, i64) {
+    paths.iter().fold(
+        (0, 0, 0),
+        |(added_data_files, added_records, added_files_size), (_, metadata)| {
+            (
+                added_data_files + 1,
+                added_records + metadata.row_count(),
+                added_files_size + metadata.size_bytes(),
+            )
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::format::{FileMetaData, RowGroup};
+
+    use super::*;
+
+    fn parquet_metadata(num_rows: i64, total_compressed_size: i64) -> DatafileMetadata {
+        DatafileMetadata::Parquet(FileMetaData {
+            num_rows,
+            row_groups: vec![RowGroup {
+                total_compressed_size,
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn added_file_stats_sums_across_files() {
+        let paths = vec![
+            ("a.parquet".to_string(), parquet_metadata(10, 100)),
+            ("b.parquet".to_string(), parquet_metadata(5, 50)),
+        ];
+
+        let (added_data_files, added_records, added_files_size) = added_file_stats(&paths);
+        assert_eq!(added_data_files, 2);
+        assert_eq!(added_records, 15);
+        assert_eq!(added_files_size, 150);
+    }
+
+    #[test]
+    fn added_file_stats_empty_is_zero() {
+        let (added_data_files, added_records, added_files_size) = added_file_stats(&[]);
+        assert_eq!(added_data_files, 0);
+        assert_eq!(added_records, 0);
+        assert_eq!(added_files_size, 0);
+    }
+}
+
+impl Operation {
+    /// Apply the operation to the table, mutating its in-memory metadata and, for
+    /// operations that add or remove data, the underlying storage. Returns this
+    /// operation's actual contribution to the new snapshot's summary.
+    pub async fn execute(&self, table: &mut Table) -> Result<WriteMetrics, Error> {
+        match self {
+            Operation::UpdateSchema(schema) => {
+                table.metadata_mut().schemas.push(schema.clone());
+                Ok(WriteMetrics::default())
+            }
+            Operation::UpdateSpec(spec_id) => {
+                table.metadata_mut().default_spec_id = *spec_id;
+                Ok(WriteMetrics::default())
+            }
+            Operation::NewAppend { paths } => {
+                // Files land pre-sorted according to the table's default sort order,
+                // if one is set, so the sort-order-id recorded on their manifest
+                // entries is meaningful. The metadata only stores the declarative,
+                // spec-shaped sort order, so convert it back to this crate's
+                // `SortOrder` to get at `sort_indices` before handing it to
+                // `append_data_files`, which sorts each file's rows with it prior to
+                // writing.
+                let metadata = table.metadata();
+                let sort_order = metadata
+                    .sort_orders
+                    .iter()
+                    .find(|order| order.order_id == metadata.default_sort_order_id)
+                    .filter(|order| !order.fields.is_empty())
+                    .map(SortOrder::try_from)
+                    .transpose()?;
+                table
+                    .append_data_files(paths.clone(), sort_order.as_ref())
+                    .await?;
+                let (added_data_files, added_records, added_files_size) = added_file_stats(paths);
+                Ok(WriteMetrics {
+                    added_data_files,
+                    added_records,
+                    added_files_size,
+                    ..Default::default()
+                })
+            }
+            Operation::UpdateProperties(entries) => {
+                table
+                    .metadata_mut()
+                    .properties
+                    .extend(entries.iter().cloned());
+                Ok(WriteMetrics::default())
+            }
+            // The entries are merged into the new snapshot's summary once it has
+            // been built, see `TableTransaction::commit`.
+            Operation::UpdateSnapshotSummary(_) => Ok(WriteMetrics::default()),
+            Operation::ReplaceSortOrder(order) => {
+                // Table metadata stores the declarative, spec-shaped sort order;
+                // this crate's `SortOrder` only exists to carry `sort_indices` for
+                // the write path, so convert before persisting it.
+                let order = iceberg_rust_spec::spec::sort::SortOrder::try_from(order)?;
+                let metadata = table.metadata_mut();
+                metadata.default_sort_order_id = order.order_id;
+                metadata.sort_orders.push(order);
+                Ok(WriteMetrics::default())
+            }
+            Operation::NewDelete { predicate } => {
+                // `delete_data_files` owns the v2 merge-on-read mechanics: it drops
+                // manifest entries for data files the predicate fully covers, and for
+                // partially-covered files writes a positional delete file and adds it
+                // to the new snapshot's manifest list as a delete file. This call site
+                // only aggregates the stats it reports back.
+                let removed = table.delete_data_files(predicate.clone()).await?;
+                Ok(WriteMetrics {
+                    deleted_data_files: removed.data_files,
+                    deleted_records: removed.records,
+                    deleted_files_size: removed.files_size,
+                    ..Default::default()
+                })
+            }
+            Operation::NewOverwrite { deleted, added } => {
+                // `overwrite_data_files` rewrites the new snapshot's manifest list in
+                // one step: the removed data files (and delete files referencing
+                // them) are dropped from it and the added ones appended. The removed
+                // files are not recorded anywhere in the manifest list.
+                let removed = table
+                    .overwrite_data_files(deleted.clone(), added.clone())
+                    .await?;
+                let (added_data_files, added_records, added_files_size) = added_file_stats(added);
+                Ok(WriteMetrics {
+                    added_data_files,
+                    added_records,
+                    added_files_size,
+                    deleted_data_files: removed.data_files,
+                    deleted_records: removed.records,
+                    deleted_files_size: removed.files_size,
+                })
+            }
+        }
+    }
+
+    /// Check whether this operation still applies cleanly to `table`, freshly
+    /// reloaded from the catalog after losing a commit race.
+    ///
+    /// Appends and the purely metadata-level operations never depend on which data
+    /// files are currently live, so they can always be rebased onto the latest
+    /// snapshot and re-executed as-is. A predicate delete re-evaluates itself against
+    /// whatever files are live when it (re-)executes, so it is likewise always safe
+    /// to rebase. An overwrite, however, names specific data files to remove: if a
+    /// concurrent snapshot already removed one of them, the overwrite can no longer
+    /// be applied and the commit must fail rather than retry.
+    pub async fn validate_rebase(&self, table: &Table) -> Result<(), Error> {
+        match self {
+            Operation::UpdateSchema(_)
+            | Operation::UpdateSpec(_)
+            | Operation::NewAppend { .. }
+            | Operation::UpdateProperties(_)
+            | Operation::UpdateSnapshotSummary(_)
+            | Operation::ReplaceSortOrder(_)
+            | Operation::NewDelete { .. } => Ok(()),
+            Operation::NewOverwrite { deleted, .. } => {
+                let live_files: std::collections::HashSet<String> = table
+                    .datafiles(None, None)
+                    .await?
+                    .into_iter()
+                    .map(|file| file.file_path)
+                    .collect();
+                if deleted.iter().any(|path| !live_files.contains(path)) {
+                    return Err(Error::InvalidFormat(
+                        "overwrite conflicts with a concurrent snapshot: one or more of its deleted files were already removed".to_string(),
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The kind of snapshot operation this contributes, for the purpose of the
+    /// snapshot summary. Purely metadata-level operations (schema/spec/property
+    /// changes) don't correspond to a snapshot operation on their own.
+    pub fn snapshot_operation(&self) -> Option<SnapshotOperation> {
+        match self {
+            Operation::NewAppend { .. } => Some(SnapshotOperation::Append),
+            Operation::NewDelete { .. } => Some(SnapshotOperation::Delete),
+            Operation::NewOverwrite { .. } => Some(SnapshotOperation::Overwrite),
+            Operation::UpdateSchema(_)
+            | Operation::UpdateSpec(_)
+            | Operation::UpdateProperties(_)
+            | Operation::UpdateSnapshotSummary(_)
+            | Operation::ReplaceSortOrder(_) => None,
+        }
+    }
+
+    /// Custom key/value pairs this operation contributes to the new snapshot's
+    /// summary, beyond the standard operation type and write statistics.
+    pub fn summary_entries(&self) -> Vec<(String, String)> {
+        match self {
+            Operation::UpdateSnapshotSummary(entries) => entries.clone(),
+            Operation::UpdateSchema(_)
+            | Operation::UpdateSpec(_)
+            | Operation::NewAppend { .. }
+            | Operation::UpdateProperties(_)
+            | Operation::ReplaceSortOrder(_)
+            | Operation::NewDelete { .. }
+            | Operation::NewOverwrite { .. } => vec![],
+        }
+    }
+}